@@ -0,0 +1,65 @@
+use crate::MediaFileInfo;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// 缓存文件名，保存在输出目录下
+pub const CACHE_FILE_NAME: &str = "nmd-cache.json";
+
+/// 一条缓存记录，除了媒体信息外还记录文件大小和修改时间，用于判断缓存是否仍然有效
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    modified: SystemTime,
+    media_info: MediaFileInfo,
+}
+
+/// 按路径缓存媒体信息（含声学指纹），避免重复扫描未变化的文件
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MediaInfoCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+}
+
+impl MediaInfoCache {
+    /// 从磁盘加载缓存，文件不存在或解析失败时返回空缓存
+    pub fn load(cache_path: &Path) -> Self {
+        match std::fs::read(cache_path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_default(),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// 将缓存写回磁盘
+    pub fn save(&self, cache_path: &Path) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self)?;
+        std::fs::write(cache_path, data)
+            .with_context(|| format!("failed to write cache to {}", cache_path.display()))
+    }
+
+    /// 查询路径对应的缓存记录，仅当文件大小和修改时间均未变化时才视为有效
+    pub fn get(&self, path: &Path, size: u64, modified: SystemTime) -> Option<&MediaFileInfo> {
+        self.entries.get(path).and_then(|entry| {
+            if entry.size == size && entry.modified == modified {
+                Some(&entry.media_info)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// 写入或更新一条缓存记录
+    pub fn insert(&mut self, path: PathBuf, size: u64, modified: SystemTime, media_info: MediaFileInfo) {
+        self.entries.insert(
+            path,
+            CacheEntry {
+                size,
+                modified,
+                media_info,
+            },
+        );
+    }
+}
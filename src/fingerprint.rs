@@ -0,0 +1,100 @@
+use anyhow::{Context, Result};
+use rusty_chromaprint::{match_fingerprints, Configuration, Fingerprinter};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// 指纹匹配所需的最少重叠时长（秒），重叠过短时比较结果不可靠
+const MIN_OVERLAP_SECONDS: f64 = 10.0;
+
+/// `--fingerprint-threshold` 的默认值：两个不同码率的同一录音重新编码后，
+/// 逐帧比特错误率通常不会是 0，因此默认给出一定容差而不是要求完全一致
+pub const DEFAULT_FINGERPRINT_THRESHOLD: f64 = 0.15;
+
+/// 解码音频数据并计算 Chromaprint 风格的声学指纹
+pub fn compute_fingerprint(audio_data: &[u8]) -> Result<Vec<u32>> {
+    let cursor = std::io::Cursor::new(audio_data.to_vec());
+    let mss = MediaSourceStream::new(Box::new(cursor), MediaSourceStreamOptions::default());
+
+    let probed = symphonia::default::get_probe().format(
+        &Hint::new(),
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .context("no playable audio track found")?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .context("unknown sample rate")?;
+    let channels = track
+        .codec_params
+        .channels
+        .context("unknown channel layout")?
+        .count() as u32;
+
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let config = Configuration::preset_test1();
+    let mut fingerprinter = Fingerprinter::new(&config);
+    fingerprinter
+        .start(sample_rate, channels)
+        .context("failed to start fingerprinter")?;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(_)) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let mut sample_buf = SampleBuffer::<i16>::new(decoded.capacity() as u64, *decoded.spec());
+                sample_buf.copy_interleaved_ref(decoded);
+                fingerprinter.consume(sample_buf.samples());
+            }
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    fingerprinter.finish();
+    Ok(fingerprinter.fingerprint().to_vec())
+}
+
+/// 判断两段声学指纹是否来自同一段录音
+///
+/// 通过对齐两段指纹并统计逐帧的汉明距离，当重叠部分足够长且比特错误率不超过
+/// `max_bit_error_rate`（对应 `--fingerprint-threshold`）时，认为两个文件是同一首歌曲的不同版本
+pub fn fingerprints_match(a: &[u32], b: &[u32], max_bit_error_rate: f64) -> bool {
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+
+    let config = Configuration::preset_test1();
+    let overlap_frames = a.len().min(b.len());
+    let overlap_seconds = overlap_frames as f64 * config.item_duration();
+    if overlap_seconds < MIN_OVERLAP_SECONDS {
+        return false;
+    }
+
+    match match_fingerprints(a, b, &config) {
+        Ok(error_rate) => error_rate <= max_bit_error_rate,
+        Err(_) => false,
+    }
+}
@@ -6,6 +6,7 @@ use crypto::{
     buffer::{self, BufferResult, ReadBuffer, WriteBuffer},
 };
 use lofty::{AudioFile, Probe};
+use rayon::prelude::*;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -13,11 +14,69 @@ use std::{
     ffi::OsStr,
     io::{self, Cursor},
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 use walkdir::{DirEntry, WalkDir};
 
+mod cache;
+mod fingerprint;
+mod report;
+
 static NETEASE_METADATA_AES_KEY: &'static [u8] = "#14ljk_!\\]&0U<'(".as_bytes();
 
+bitflags::bitflags! {
+    /// 判断两个文件是否为同一首歌曲时需要比对的标签字段
+    ///
+    /// 歌曲名始终是分组的第一依据（见 `track_name_map`），不受此处比对项控制
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct Similarity: u32 {
+        const ARTIST  = 0b000_0010;
+        const ALBUM   = 0b000_0100;
+        const YEAR    = 0b000_1000;
+        const GENRE   = 0b001_0000;
+        const LENGTH  = 0b010_0000;
+        const BITRATE = 0b100_0000;
+    }
+}
+
+impl std::str::FromStr for Similarity {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "ARTIST" => Ok(Similarity::ARTIST),
+            "ALBUM" => Ok(Similarity::ALBUM),
+            "YEAR" => Ok(Similarity::YEAR),
+            "GENRE" => Ok(Similarity::GENRE),
+            "LENGTH" => Ok(Similarity::LENGTH),
+            "BITRATE" => Ok(Similarity::BITRATE),
+            _ => Err(format!("unknown --similarity criterion: {}", s)),
+        }
+    }
+}
+
+/// 去重后对幸存文件 / 被淘汰的重复文件的处理方式
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Action {
+    /// 复制幸存文件到输出目录，原始文件保持不变（默认）
+    Copy,
+    /// 将幸存文件移动到输出目录
+    Move,
+    /// 不做复制或移动，直接删除被淘汰的重复文件
+    Delete,
+}
+
+/// 多个重复文件中保留哪一个版本
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum KeepPolicy {
+    /// 保留码率更高、时长更长的版本（默认）
+    BestQuality,
+    /// 保留修改时间最新的版本
+    Newest,
+    /// 保留修改时间最旧的版本
+    Oldest,
+}
+
 /// 网易云音乐下载文件去重工具
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -33,24 +92,91 @@ struct Args {
     /// 不输出任何文件，仅查看运行结果
     #[clap(short, long, value_parser, default_value_t = false)]
     dry_run: bool,
+
+    /// 判断两个文件是否为同一首歌曲时需要比对的标签字段，多个值用逗号分隔
+    /// 可选值：artist, album, year, genre, length, bitrate（不区分大小写）
+    /// 歌曲名始终参与分组，不在此列表中
+    #[clap(long, value_parser, value_delimiter = ',', default_values = ["album"])]
+    similarity: Vec<String>,
+
+    /// 比对项包含 length 时，两个文件允许的最大时长差异（秒）
+    #[clap(long, value_parser, default_value_t = 1.5)]
+    max_length_diff: f64,
+
+    /// 标签比对结果不可靠时（例如按专辑去重但缺少专辑信息），改用声学指纹确认
+    /// 两个文件是否为同一首歌曲，此参数是两段指纹允许的最大逐帧比特错误率
+    #[clap(long, value_parser, default_value_t = fingerprint::DEFAULT_FINGERPRINT_THRESHOLD)]
+    fingerprint_threshold: f64,
+
+    /// 去重后对幸存文件 / 被淘汰的重复文件的处理方式
+    #[clap(long, value_enum, default_value_t = Action::Copy)]
+    action: Action,
+
+    /// 多个重复文件中保留哪一个版本
+    #[clap(long, value_enum, default_value_t = KeepPolicy::BestQuality)]
+    keep: KeepPolicy,
+
+    /// 将去重结果输出为结构化报告，按扩展名选择格式（.csv 输出 CSV，否则输出 JSON）
+    #[clap(long, value_parser, required = false)]
+    report: Option<String>,
 }
 
 /// 媒体文件信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct MediaFileInfo {
     file_path: PathBuf,
     music_id: Option<u64>,
     album: Option<String>,
+    artist: Option<String>,
+    year: Option<u32>,
+    genre: Option<String>,
     track_name: String,
     bitrate: u32,
     duration: u128,
+    modified: SystemTime,
+    fingerprint: Option<Vec<u32>>,
 }
 
 impl MediaFileInfo {
+    /// 按照给定的比对标准，判断两个文件是否为同一首歌曲的不同版本
+    pub fn matches(&self, other: &MediaFileInfo, similarity: Similarity, max_length_diff: f64) -> bool {
+        if similarity.contains(Similarity::ARTIST) && self.artist != other.artist {
+            return false;
+        }
+        if similarity.contains(Similarity::ALBUM) && self.album != other.album {
+            return false;
+        }
+        if similarity.contains(Similarity::YEAR) && self.year != other.year {
+            return false;
+        }
+        if similarity.contains(Similarity::GENRE) && self.genre != other.genre {
+            return false;
+        }
+        if similarity.contains(Similarity::LENGTH) {
+            let diff_seconds = self.duration.abs_diff(other.duration) as f64 / 1000.0;
+            if diff_seconds > max_length_diff {
+                return false;
+            }
+        }
+        if similarity.contains(Similarity::BITRATE) && self.bitrate != other.bitrate {
+            return false;
+        }
+        true
+    }
+
     pub fn better_than(&self, other: &MediaFileInfo) -> bool {
         other.bitrate < self.bitrate
             || (other.bitrate == self.bitrate && other.duration < self.duration)
     }
+
+    /// 按照用户选择的 --keep 策略，判断应该保留自己还是 other
+    pub fn is_preferred_over(&self, other: &MediaFileInfo, keep: KeepPolicy) -> bool {
+        match keep {
+            KeepPolicy::BestQuality => self.better_than(other),
+            KeepPolicy::Newest => self.modified > other.modified,
+            KeepPolicy::Oldest => self.modified < other.modified,
+        }
+    }
 }
 
 /// 网易云音乐标签
@@ -100,6 +226,18 @@ fn get_media_file_info<P: AsRef<Path>>(file_path: &P) -> Result<MediaFileInfo> {
         .get_texts(&lofty::ItemKey::AlbumTitle)
         .next()
         .map(|s| s.to_string());
+    let artist = tag
+        .get_texts(&lofty::ItemKey::TrackArtist)
+        .next()
+        .map(|s| s.to_string());
+    let year = tag
+        .get_texts(&lofty::ItemKey::Year)
+        .next()
+        .and_then(|s| s.parse::<u32>().ok());
+    let genre = tag
+        .get_texts(&lofty::ItemKey::Genre)
+        .next()
+        .map(|s| s.to_string());
     let mut track_name = tag
         .get_texts(&lofty::ItemKey::TrackTitle)
         .next()
@@ -118,13 +256,45 @@ fn get_media_file_info<P: AsRef<Path>>(file_path: &P) -> Result<MediaFileInfo> {
         music_id = Some(ncm_metadata.music_id);
     }
 
+    // 计算声学指纹，用于在没有 music id 时辅助判断是否为同一首歌曲
+    // 有 music id 的文件跳过这一步昂贵的解码运算，不计算指纹；之后按歌曲名比对时，
+    // 缺少指纹的一方会退回到标签比对的结果
+    let fingerprint = if music_id.is_none() {
+        let audio_bytes = match &decryped_data {
+            Some(data) => data.clone(),
+            None => std::fs::read(file_path)?,
+        };
+        match fingerprint::compute_fingerprint(&audio_bytes) {
+            Ok(fp) => Some(fp),
+            Err(e) => {
+                eprintln!(
+                    "failed to compute fingerprint for {}: {}",
+                    file_path_buf.to_str().unwrap(),
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let modified = std::fs::metadata(file_path)?
+        .modified()
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+
     Ok(MediaFileInfo {
         file_path: file_path_buf,
         music_id: music_id,
         album: album,
+        artist: artist,
+        year: year,
+        genre: genre,
         track_name: track_name.unwrap(),
         bitrate: tagged_file.properties().audio_bitrate().unwrap_or(0),
         duration: tagged_file.properties().duration().as_millis(),
+        modified,
+        fingerprint,
     })
 }
 
@@ -176,25 +346,40 @@ fn decrypt_163_key(key: &str) -> Result<NeteaseKey> {
 fn update_media_info(
     id_map: &mut HashMap<u64, MediaFileInfo>,
     without_id_list: &mut Vec<MediaFileInfo>,
-    dir_entry: &DirEntry,
-) -> Result<()> {
-    let file_info = get_media_file_info(&dir_entry.path())?;
+    removed_files: &mut Vec<PathBuf>,
+    report_records: &mut Vec<report::DuplicateRecord>,
+    file_info: MediaFileInfo,
+    keep: KeepPolicy,
+) {
     let music_id = file_info.music_id;
     match music_id {
         Some(music_id) => {
             // 有 music id，先去重
-            if let Some(old_file_info) = id_map.get(&music_id) {
+            if let Some(old_file_info) = id_map.get(&music_id).cloned() {
                 println!(
                     "duplicate music id found: \n -- 1. {}\n -- 2. {}",
                     &file_info.file_path.to_str().unwrap(),
                     old_file_info.file_path.to_str().unwrap()
                 );
-                // 新的文件比特率更高或者比特率相同但时长更长，则替换 map 中的数据
-                // 因为网易云音乐会不定期更新一些低音质的文件
-                if file_info.better_than(old_file_info) {
-                    // 保留码率更高的版本，如果码率一致，保留时长更长的版本
+                // 根据 --keep 策略，决定保留新文件还是原有的文件
+                if file_info.is_preferred_over(&old_file_info, keep) {
+                    report_records.push(report::DuplicateRecord::new(
+                        &file_info,
+                        &old_file_info,
+                        keep,
+                        report::MatchMethod::MusicId,
+                    ));
+                    removed_files.push(old_file_info.file_path);
                     id_map.insert(music_id, file_info);
                     println!("    and 1 better than 2");
+                } else {
+                    report_records.push(report::DuplicateRecord::new(
+                        &old_file_info,
+                        &file_info,
+                        keep,
+                        report::MatchMethod::MusicId,
+                    ));
+                    removed_files.push(file_info.file_path);
                 }
             } else {
                 id_map.insert(music_id, file_info);
@@ -205,7 +390,6 @@ fn update_media_info(
             without_id_list.push(file_info);
         }
     }
-    Ok(())
 }
 
 /// 获取不包含 (1) 等计数的文件名
@@ -238,44 +422,97 @@ fn set_file_name_count(file_name: &PathBuf, count: i32) -> PathBuf {
     }
 }
 
-/// 输出文件到目标目录
-fn write_out_media_file(
+/// 根据 --action 处理去重结果：复制/移动幸存文件，或直接删除被淘汰的重复文件
+fn apply_action(
     track_name_map: &HashMap<String, Vec<MediaFileInfo>>,
+    removed_files: &[PathBuf],
     output_dir: &PathBuf,
+    action: Action,
     dry_run: bool,
 ) {
-    track_name_map
-        .values()
-        .flat_map(|vec| vec.iter())
-        .for_each(|file_info| {
-            let from_path = &file_info.file_path;
-            // 获取文件名并去除计数
-            let filename = get_file_name_without_count(from_path);
-            let mut output_filename = output_dir.join(&filename);
-            let mut count = 0;
-            while output_filename.exists() {
-                count = count + 1;
-                // 如果文件已存在则添加计数
-                output_filename = output_dir.join(set_file_name_count(&filename, count));
-            }
-
-            println!(
-                "copy file from {}\n            to {}",
-                &from_path.to_str().unwrap(),
-                &output_filename.to_str().unwrap()
-            );
-            if !dry_run {
-                if let Err(e) = std::fs::copy(from_path, output_filename) {
-                    eprintln!("{}", e);
+    match action {
+        Action::Copy | Action::Move => {
+            track_name_map
+                .values()
+                .flat_map(|vec| vec.iter())
+                .for_each(|file_info| {
+                    let from_path = &file_info.file_path;
+                    // 获取文件名并去除计数
+                    let filename = get_file_name_without_count(from_path);
+                    let mut output_filename = output_dir.join(&filename);
+                    let mut count = 0;
+                    while output_filename.exists() {
+                        count = count + 1;
+                        // 如果文件已存在则添加计数
+                        output_filename = output_dir.join(set_file_name_count(&filename, count));
+                    }
+
+                    if action == Action::Copy {
+                        println!(
+                            "copy file from {}\n            to {}",
+                            &from_path.to_str().unwrap(),
+                            &output_filename.to_str().unwrap()
+                        );
+                        if !dry_run {
+                            if let Err(e) = std::fs::copy(from_path, output_filename) {
+                                eprintln!("{}", e);
+                            }
+                        }
+                    } else {
+                        println!(
+                            "move file from {}\n            to {}",
+                            &from_path.to_str().unwrap(),
+                            &output_filename.to_str().unwrap()
+                        );
+                        if !dry_run {
+                            if let Err(e) = std::fs::rename(from_path, output_filename) {
+                                eprintln!("{}", e);
+                            }
+                        }
+                    }
+                });
+        }
+        Action::Delete => {
+            // 幸存文件原地保留，只删除被淘汰的重复文件
+            for path in removed_files {
+                println!("delete duplicate file: {}", path.to_str().unwrap());
+                if !dry_run {
+                    if let Err(e) = std::fs::remove_file(path) {
+                        eprintln!("{}", e);
+                    }
                 }
             }
-        });
+        }
+    }
+}
+
+/// 判断两个文件的标签比对结果是否可靠
+///
+/// 当启用的比对项是可选字段（专辑、艺术家、年份、流派）且双方都缺失该字段时，
+/// 两者恰好相等只是因为都是 None，并不能说明是同一首歌曲，此时需要用声学指纹进一步确认
+fn has_reliable_tag_match(a: &MediaFileInfo, b: &MediaFileInfo, similarity: Similarity) -> bool {
+    let both_missing = |flag: Similarity, x_missing: bool, y_missing: bool| {
+        similarity.contains(flag) && x_missing && y_missing
+    };
+    !both_missing(Similarity::ALBUM, a.album.is_none(), b.album.is_none())
+        && !both_missing(Similarity::ARTIST, a.artist.is_none(), b.artist.is_none())
+        && !both_missing(Similarity::YEAR, a.year.is_none(), b.year.is_none())
+        && !both_missing(Similarity::GENRE, a.genre.is_none(), b.genre.is_none())
 }
 
 trait TrackNameMap {
     fn add_media_info(&mut self, media_info: &MediaFileInfo);
     fn is_exists(&self, track_name: &String, album: &String) -> bool;
-    fn replace_media_info(&mut self, media_info: &MediaFileInfo);
+    fn replace_media_info(
+        &mut self,
+        media_info: &MediaFileInfo,
+        similarity: Similarity,
+        max_length_diff: f64,
+        fingerprint_threshold: f64,
+        keep: KeepPolicy,
+        removed_files: &mut Vec<PathBuf>,
+        report_records: &mut Vec<report::DuplicateRecord>,
+    );
 }
 
 impl TrackNameMap for HashMap<String, Vec<MediaFileInfo>> {
@@ -298,36 +535,76 @@ impl TrackNameMap for HashMap<String, Vec<MediaFileInfo>> {
         }
     }
 
-    fn replace_media_info(&mut self, media_info: &MediaFileInfo) {
+    fn replace_media_info(
+        &mut self,
+        media_info: &MediaFileInfo,
+        similarity: Similarity,
+        max_length_diff: f64,
+        fingerprint_threshold: f64,
+        keep: KeepPolicy,
+        removed_files: &mut Vec<PathBuf>,
+        report_records: &mut Vec<report::DuplicateRecord>,
+    ) {
         let inner_vec = self.entry(media_info.track_name.clone()).or_default();
         // 检查是否已存在相似的音乐
         let mut has_similar = false;
         let mut similar_pos = usize::MAX;
+        let mut match_method = report::MatchMethod::TagMatch;
         for (pos, old_media_info) in inner_vec.iter().enumerate() {
-            // 对于没有专辑信息的音乐，因为不确定是否为同名音乐，所以判断结果不可靠
-            // 可能需要引入音频指纹
-            // 如果长度差异在 1.5 秒内，视为相似的音乐
-            let has_null_album = old_media_info.album == None || media_info.album == None;
-            if old_media_info.album == media_info.album
-                || (has_null_album && old_media_info.duration.abs_diff(media_info.duration) < 1500)
-            {
-                has_similar = true;
-                similar_pos = pos;
-
-                println!(
-                    "★ probably duplicate music found: \n -- 1. {}\n -- 2. {}",
-                    &media_info.file_path.to_str().unwrap(),
-                    old_media_info.file_path.to_str().unwrap()
-                );
+            if !media_info.matches(old_media_info, similarity, max_length_diff) {
+                continue;
             }
+
+            // 如果比对项中有可选字段（专辑/艺术家/年份/流派）且双方都缺失，仅凭标签无法
+            // 确认是否为同一首歌曲，用声学指纹加以确认
+            let method = if has_reliable_tag_match(media_info, old_media_info, similarity) {
+                report::MatchMethod::TagMatch
+            } else {
+                let confirmed = match (&old_media_info.fingerprint, &media_info.fingerprint) {
+                    (Some(fp1), Some(fp2)) => {
+                        fingerprint::fingerprints_match(fp1, fp2, fingerprint_threshold)
+                    }
+                    // 没有指纹数据时，退回到标签比对的结果
+                    _ => true,
+                };
+                if !confirmed {
+                    continue;
+                }
+                report::MatchMethod::FingerprintConfirmed
+            };
+
+            has_similar = true;
+            similar_pos = pos;
+            match_method = method;
+
+            println!(
+                "★ probably duplicate music found: \n -- 1. {}\n -- 2. {}",
+                &media_info.file_path.to_str().unwrap(),
+                old_media_info.file_path.to_str().unwrap()
+            );
         }
-        // 比对当前音乐和相似的音乐哪一个更好
+        // 按 --keep 策略比对当前音乐和相似的音乐，保留其中一个
         if has_similar {
             let similar_media_info = inner_vec.get(similar_pos).unwrap();
-            if media_info.better_than(similar_media_info) {
-                inner_vec.remove(similar_pos);
+            if media_info.is_preferred_over(similar_media_info, keep) {
+                report_records.push(report::DuplicateRecord::new(
+                    media_info,
+                    similar_media_info,
+                    keep,
+                    match_method,
+                ));
+                let replaced = inner_vec.remove(similar_pos);
+                removed_files.push(replaced.file_path);
                 inner_vec.push(media_info.clone());
                 println!("    and 1 better than 2");
+            } else {
+                report_records.push(report::DuplicateRecord::new(
+                    similar_media_info,
+                    media_info,
+                    keep,
+                    match_method,
+                ));
+                removed_files.push(media_info.file_path.clone());
             }
         } else {
             inner_vec.push(media_info.clone());
@@ -338,6 +615,21 @@ impl TrackNameMap for HashMap<String, Vec<MediaFileInfo>> {
 fn main() {
     let cli = Args::parse();
 
+    let similarity = cli.similarity.iter().try_fold(Similarity::empty(), |acc, s| {
+        s.parse::<Similarity>().map(|flag| acc | flag)
+    });
+    let similarity = match similarity {
+        Ok(similarity) => similarity,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let max_length_diff = cli.max_length_diff;
+    let fingerprint_threshold = cli.fingerprint_threshold;
+    let keep = cli.keep;
+    let action = cli.action;
+
     let output_dir = match cli.output {
         Some(output) => PathBuf::from(output),
         None => PathBuf::from(std::env::current_exe().unwrap())
@@ -361,11 +653,8 @@ fn main() {
         .map(|it| it.to_string())
         .collect();
 
-    // 对于拥有 music id 的媒体文件，根据 music id 先进行去重并保留最佳质量版本
-    let mut id_map: HashMap<u64, MediaFileInfo> = HashMap::new();
-    // 记录没有 music id 的媒体文件
-    let mut without_id_list: Vec<MediaFileInfo> = vec![];
-
+    // 先收集所有待处理文件的路径，提取媒体信息的耗时操作之后交给 rayon 并行处理
+    let mut entries: Vec<DirEntry> = vec![];
     for input_dir in cli.input {
         let walker = WalkDir::new(input_dir)
             .follow_links(true)
@@ -388,9 +677,69 @@ fn main() {
                 continue;
             }
 
-            if let Err(e) = update_media_info(&mut id_map, &mut without_id_list, &entry) {
-                eprintln!("file: {}, error: {}", entry.path().to_str().unwrap(), e);
+            entries.push(entry);
+        }
+    }
+
+    // 加载缓存，避免重复扫描尚未发生变化的文件
+    let cache_path = output_dir.join(cache::CACHE_FILE_NAME);
+    let cache = cache::MediaInfoCache::load(&cache_path);
+
+    println!("extracting media info from {} files...", entries.len());
+    let extracted: Vec<(PathBuf, u64, SystemTime, MediaFileInfo)> = entries
+        .par_iter()
+        .filter_map(|entry| {
+            let path = entry.path().to_path_buf();
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    eprintln!("file: {}, error: {}", path.to_str().unwrap(), e);
+                    return None;
+                }
+            };
+            let size = metadata.len();
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+
+            if let Some(cached_info) = cache.get(&path, size, modified) {
+                return Some((path, size, modified, cached_info.clone()));
+            }
+
+            match get_media_file_info(&path) {
+                Ok(file_info) => Some((path, size, modified, file_info)),
+                Err(e) => {
+                    eprintln!("file: {}, error: {}", path.to_str().unwrap(), e);
+                    None
+                }
             }
+        })
+        .collect();
+
+    // 对于拥有 music id 的媒体文件，根据 music id 先进行去重并按 --keep 策略保留其中一个版本
+    let mut id_map: HashMap<u64, MediaFileInfo> = HashMap::new();
+    // 记录没有 music id 的媒体文件
+    let mut without_id_list: Vec<MediaFileInfo> = vec![];
+    // 记录被淘汰的重复文件，供 --action delete 使用
+    let mut removed_files: Vec<PathBuf> = vec![];
+    // 记录每一次去重决策，供 --report 使用
+    let mut report_records: Vec<report::DuplicateRecord> = vec![];
+
+    // 合并去重、更新缓存都在单线程中进行，保证 HashMap 的更新是正确的
+    let mut cache = cache;
+    for (path, size, modified, file_info) in extracted {
+        cache.insert(path, size, modified, file_info.clone());
+        update_media_info(
+            &mut id_map,
+            &mut without_id_list,
+            &mut removed_files,
+            &mut report_records,
+            file_info,
+            keep,
+        );
+    }
+
+    if !cli.dry_run {
+        if let Err(e) = cache.save(&cache_path) {
+            eprintln!("failed to save cache: {}", e);
         }
     }
 
@@ -404,15 +753,96 @@ fn main() {
         .values()
         .for_each(|media_info| track_name_map.add_media_info(media_info));
     println!("checking...");
-    without_id_list
-        .iter()
-        .for_each(|media_info| track_name_map.replace_media_info(media_info));
+    without_id_list.iter().for_each(|media_info| {
+        track_name_map.replace_media_info(
+            media_info,
+            similarity,
+            max_length_diff,
+            fingerprint_threshold,
+            keep,
+            &mut removed_files,
+            &mut report_records,
+        )
+    });
+
+    if let Some(report_path) = cli.report {
+        let report_path = PathBuf::from(report_path);
+        println!("writing report to {}...", report_path.to_str().unwrap());
+        if let Err(e) = report::write_report(&report_records, &report_path) {
+            eprintln!("failed to write report: {}", e);
+        }
+    }
 
-    println!("copy music to output dir? (y/N): ");
+    println!("apply changes? (y/N): ");
     let mut input_string = String::new();
     input_string.clear();
     io::stdin().read_line(&mut input_string).unwrap();
     if input_string.trim().to_lowercase() == "y" {
-        write_out_media_file(&track_name_map, &output_dir, cli.dry_run);
+        apply_action(&track_name_map, &removed_files, &output_dir, action, cli.dry_run);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn media_info(album: Option<&str>, artist: Option<&str>, year: Option<u32>, genre: Option<&str>) -> MediaFileInfo {
+        MediaFileInfo {
+            file_path: PathBuf::new(),
+            music_id: None,
+            album: album.map(String::from),
+            artist: artist.map(String::from),
+            year,
+            genre: genre.map(String::from),
+            track_name: "track".to_string(),
+            bitrate: 0,
+            duration: 0,
+            modified: SystemTime::UNIX_EPOCH,
+            fingerprint: None,
+        }
+    }
+
+    #[test]
+    fn reliable_when_compared_field_not_enabled() {
+        let a = media_info(None, Some("artist"), None, None);
+        let b = media_info(None, Some("artist"), None, None);
+        // 两者都没有专辑信息，但 --similarity 没有启用 ALBUM，不受影响
+        assert!(has_reliable_tag_match(&a, &b, Similarity::ARTIST));
+    }
+
+    #[test]
+    fn unreliable_when_both_missing_compared_field() {
+        let a = media_info(None, Some("artist"), None, None);
+        let b = media_info(None, Some("artist"), None, None);
+        // 双方都缺少专辑信息，仅凭 "两者都是 None" 不能确认是同一首歌
+        assert!(!has_reliable_tag_match(&a, &b, Similarity::ALBUM | Similarity::ARTIST));
+    }
+
+    #[test]
+    fn reliable_when_only_one_side_missing_compared_field() {
+        let a = media_info(Some("album"), None, None, None);
+        let b = media_info(None, None, None, None);
+        // 只有一方缺少专辑信息，matches() 已经会判定为不相似，不需要指纹确认
+        assert!(has_reliable_tag_match(&a, &b, Similarity::ALBUM));
+    }
+
+    #[test]
+    fn reliable_when_both_sides_have_compared_field() {
+        let a = media_info(Some("album"), None, None, None);
+        let b = media_info(Some("album"), None, None, None);
+        assert!(has_reliable_tag_match(&a, &b, Similarity::ALBUM));
+    }
+
+    #[test]
+    fn unreliable_checks_artist_year_genre_not_just_album() {
+        let a = media_info(Some("album"), None, None, None);
+        let b = media_info(Some("album"), None, None, None);
+        assert!(!has_reliable_tag_match(&a, &b, Similarity::ALBUM | Similarity::ARTIST));
+        assert!(!has_reliable_tag_match(&a, &b, Similarity::ALBUM | Similarity::YEAR));
+        assert!(!has_reliable_tag_match(&a, &b, Similarity::ALBUM | Similarity::GENRE));
+
+        let a = media_info(Some("album"), None, None, None);
+        let b = media_info(Some("album"), None, None, None);
+        assert!(has_reliable_tag_match(&a, &b, Similarity::ALBUM));
     }
 }
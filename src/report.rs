@@ -0,0 +1,236 @@
+use crate::{KeepPolicy, MediaFileInfo};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// 两个文件被判定为同一首歌曲所依据的方式
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMethod {
+    /// 双方 music id 相同
+    MusicId,
+    /// 按 --similarity 指定的标签字段比对得出
+    TagMatch,
+    /// 标签比对结果不可靠，由声学指纹确认
+    FingerprintConfirmed,
+}
+
+/// 推断胜出版本优于被淘汰版本的原因
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    HigherBitrate,
+    LongerDuration,
+    FingerprintMatch,
+    /// 码率、时长均相同，且并非由声学指纹确认，无法进一步区分两者的优劣
+    Identical,
+    NewerModifiedTime,
+    OlderModifiedTime,
+}
+
+impl Reason {
+    /// 按 --keep 策略、两个文件的标签信息及匹配方式，推断为什么保留了 winner 而不是 loser
+    pub fn infer(winner: &MediaFileInfo, loser: &MediaFileInfo, keep: KeepPolicy, method: MatchMethod) -> Self {
+        match keep {
+            KeepPolicy::Newest => Reason::NewerModifiedTime,
+            KeepPolicy::Oldest => Reason::OlderModifiedTime,
+            KeepPolicy::BestQuality => {
+                if winner.bitrate != loser.bitrate {
+                    Reason::HigherBitrate
+                } else if winner.duration != loser.duration {
+                    Reason::LongerDuration
+                } else if matches!(method, MatchMethod::FingerprintConfirmed) {
+                    Reason::FingerprintMatch
+                } else {
+                    // 码率、时长都相同，但并非靠声学指纹确认，不能归因于指纹匹配
+                    Reason::Identical
+                }
+            }
+        }
+    }
+}
+
+/// 报告中单个文件成员的信息
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportEntry {
+    pub file_path: PathBuf,
+    pub music_id: Option<u64>,
+    pub track_name: String,
+    pub album: Option<String>,
+    pub bitrate: u32,
+    pub duration: u128,
+}
+
+impl From<&MediaFileInfo> for ReportEntry {
+    fn from(info: &MediaFileInfo) -> Self {
+        ReportEntry {
+            file_path: info.file_path.clone(),
+            music_id: info.music_id,
+            track_name: info.track_name.clone(),
+            album: info.album.clone(),
+            bitrate: info.bitrate,
+            duration: info.duration,
+        }
+    }
+}
+
+/// 一条去重决策记录：哪个文件被保留、哪个被淘汰，以及原因
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateRecord {
+    pub kept: ReportEntry,
+    pub discarded: ReportEntry,
+    pub reason: Reason,
+}
+
+impl DuplicateRecord {
+    pub fn new(kept: &MediaFileInfo, discarded: &MediaFileInfo, keep: KeepPolicy, method: MatchMethod) -> Self {
+        DuplicateRecord {
+            reason: Reason::infer(kept, discarded, keep, method),
+            kept: ReportEntry::from(kept),
+            discarded: ReportEntry::from(discarded),
+        }
+    }
+}
+
+/// CSV 输出用的扁平记录，字段名会作为表头
+#[derive(Debug, Clone, Serialize)]
+struct ReportRow {
+    kept_file_path: String,
+    kept_music_id: Option<u64>,
+    kept_track_name: String,
+    kept_album: Option<String>,
+    kept_bitrate: u32,
+    kept_duration: u128,
+    discarded_file_path: String,
+    discarded_music_id: Option<u64>,
+    discarded_track_name: String,
+    discarded_album: Option<String>,
+    discarded_bitrate: u32,
+    discarded_duration: u128,
+    reason: String,
+}
+
+impl From<&DuplicateRecord> for ReportRow {
+    fn from(record: &DuplicateRecord) -> Self {
+        ReportRow {
+            kept_file_path: record.kept.file_path.to_string_lossy().to_string(),
+            kept_music_id: record.kept.music_id,
+            kept_track_name: record.kept.track_name.clone(),
+            kept_album: record.kept.album.clone(),
+            kept_bitrate: record.kept.bitrate,
+            kept_duration: record.kept.duration,
+            discarded_file_path: record.discarded.file_path.to_string_lossy().to_string(),
+            discarded_music_id: record.discarded.music_id,
+            discarded_track_name: record.discarded.track_name.clone(),
+            discarded_album: record.discarded.album.clone(),
+            discarded_bitrate: record.discarded.bitrate,
+            discarded_duration: record.discarded.duration,
+            // 复用 Reason 的 Serialize 实现（snake_case），保证 CSV 和 JSON 输出的取值一致
+            reason: match serde_json::to_value(record.reason) {
+                Ok(serde_json::Value::String(s)) => s,
+                _ => format!("{:?}", record.reason),
+            },
+        }
+    }
+}
+
+/// 将去重决策写出为结构化报告，供用户审计去重结果或编写自己的清理脚本
+///
+/// 根据 `report_path` 的扩展名选择输出格式：`.csv` 输出 CSV，否则输出 JSON
+pub fn write_report(records: &[DuplicateRecord], report_path: &Path) -> Result<()> {
+    let is_csv = report_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("csv"));
+
+    if is_csv {
+        let mut writer = csv::Writer::from_path(report_path)
+            .with_context(|| format!("failed to create report at {}", report_path.display()))?;
+        for record in records {
+            writer.serialize(ReportRow::from(record))?;
+        }
+        writer.flush()?;
+    } else {
+        let data = serde_json::to_vec_pretty(records)?;
+        std::fs::write(report_path, data)
+            .with_context(|| format!("failed to write report to {}", report_path.display()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::SystemTime;
+
+    fn media_info(bitrate: u32, duration: u128) -> MediaFileInfo {
+        MediaFileInfo {
+            file_path: PathBuf::new(),
+            music_id: None,
+            album: None,
+            artist: None,
+            year: None,
+            genre: None,
+            track_name: "track".to_string(),
+            bitrate,
+            duration,
+            modified: SystemTime::UNIX_EPOCH,
+            fingerprint: None,
+        }
+    }
+
+    #[test]
+    fn keep_newest_and_oldest_ignore_tags() {
+        let winner = media_info(128, 1000);
+        let loser = media_info(320, 2000);
+        assert!(matches!(
+            Reason::infer(&winner, &loser, KeepPolicy::Newest, MatchMethod::MusicId),
+            Reason::NewerModifiedTime
+        ));
+        assert!(matches!(
+            Reason::infer(&winner, &loser, KeepPolicy::Oldest, MatchMethod::MusicId),
+            Reason::OlderModifiedTime
+        ));
+    }
+
+    #[test]
+    fn best_quality_prefers_bitrate_then_duration() {
+        let higher_bitrate = media_info(320, 1000);
+        let lower_bitrate = media_info(128, 1000);
+        assert!(matches!(
+            Reason::infer(&higher_bitrate, &lower_bitrate, KeepPolicy::BestQuality, MatchMethod::MusicId),
+            Reason::HigherBitrate
+        ));
+
+        let longer = media_info(128, 2000);
+        let shorter = media_info(128, 1000);
+        assert!(matches!(
+            Reason::infer(&longer, &shorter, KeepPolicy::BestQuality, MatchMethod::MusicId),
+            Reason::LongerDuration
+        ));
+    }
+
+    #[test]
+    fn best_quality_identical_tie_only_reports_fingerprint_match_when_confirmed() {
+        let a = media_info(128, 1000);
+        let b = media_info(128, 1000);
+
+        // 码率、时长相同，但是靠 music id / 标签比对得出，不能归因于指纹匹配
+        assert!(matches!(
+            Reason::infer(&a, &b, KeepPolicy::BestQuality, MatchMethod::MusicId),
+            Reason::Identical
+        ));
+        assert!(matches!(
+            Reason::infer(&a, &b, KeepPolicy::BestQuality, MatchMethod::TagMatch),
+            Reason::Identical
+        ));
+
+        // 只有真正由声学指纹确认时，才能归因于指纹匹配
+        assert!(matches!(
+            Reason::infer(&a, &b, KeepPolicy::BestQuality, MatchMethod::FingerprintConfirmed),
+            Reason::FingerprintMatch
+        ));
+    }
+}